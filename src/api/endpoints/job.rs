@@ -0,0 +1,45 @@
+use crate::job::{JobId, JobQueue, PrintJob};
+use rocket::{
+    delete, get, http::Status, post, put, response::status, serde::json::Json, Responder, State,
+};
+use std::path::PathBuf;
+
+#[get("/jobs")]
+pub fn get(queue: &State<JobQueue>) -> Json<Vec<PrintJob>> {
+    Json(queue.list())
+}
+
+#[derive(Responder)]
+pub enum ApiPostJobResponse {
+    #[response(status = 200)]
+    Ok(Json<JobId>),
+    // source wasn't a path inside the configured gcode directory
+    #[response(status = 422)]
+    InvalidSource(()),
+}
+
+#[post("/jobs", data = "<source>")]
+pub fn post(source: Json<PathBuf>, queue: &State<JobQueue>) -> ApiPostJobResponse {
+    match queue.submit(source.into_inner()) {
+        Some(id) => ApiPostJobResponse::Ok(Json(id)),
+        None => ApiPostJobResponse::InvalidSource(()),
+    }
+}
+
+#[put("/jobs/<id>/position?<position>")]
+pub fn put_position(id: JobId, position: usize, queue: &State<JobQueue>) -> status::Custom<()> {
+    if queue.reorder(id, position) {
+        status::Custom(Status::Ok, ())
+    } else {
+        status::Custom(Status::NotFound, ())
+    }
+}
+
+#[delete("/jobs/<id>")]
+pub fn delete(id: JobId, queue: &State<JobQueue>) -> status::Custom<()> {
+    if queue.cancel(id) {
+        status::Custom(Status::Ok, ())
+    } else {
+        status::Custom(Status::NotFound, ())
+    }
+}
@@ -2,6 +2,7 @@ pub mod axis;
 pub mod error;
 pub mod gcode;
 pub mod heating;
+pub mod job;
 
 use crate::{
     api::values::ApiError,
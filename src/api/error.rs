@@ -1,17 +1,38 @@
-use rocket::{get, http::Status, response::status};
+use crate::{
+    api::values::ApiError,
+    error_store::{ErrorStore, StoredError},
+};
+use rocket::{get, serde::json::Json, Responder, State};
+
+#[derive(Responder)]
+pub enum ApiGetErrorResponse {
+    #[response(status = 200)]
+    Ok(Json<StoredError>),
+    #[response(status = 404)]
+    NotFound(Json<ApiError>),
+}
 
 #[get("/errors?<page>")]
-pub fn get(page: Option<usize>) -> status::Custom<&'static str> {
-    let page = page.unwrap_or(0);
-    status::Custom(Status::NotImplemented, "unimplemented")
+pub fn get(page: Option<usize>, error_store: &State<ErrorStore>) -> Json<Vec<StoredError>> {
+    Json(error_store.page(page.unwrap_or(0)))
 }
 
 #[get("/error/last")]
-pub fn get_last() -> status::Custom<&'static str> {
-    status::Custom(Status::NotImplemented, "unimplemented")
+pub fn get_last(error_store: &State<ErrorStore>) -> ApiGetErrorResponse {
+    match error_store.last() {
+        Some(error) => ApiGetErrorResponse::Ok(Json(error)),
+        None => ApiGetErrorResponse::NotFound(Json(ApiError {
+            message: "no errors have been recorded yet".to_owned(),
+        })),
+    }
 }
 
 #[get("/error/<id>")]
-pub fn get_id(id: usize) -> status::Custom<&'static str> {
-    status::Custom(Status::NotImplemented, "unimplemented")
+pub fn get_id(id: usize, error_store: &State<ErrorStore>) -> ApiGetErrorResponse {
+    match error_store.get(id) {
+        Some(error) => ApiGetErrorResponse::Ok(Json(error)),
+        None => ApiGetErrorResponse::NotFound(Json(ApiError {
+            message: format!("no error with id {} (it may have been evicted)", id),
+        })),
+    }
 }
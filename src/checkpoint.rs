@@ -0,0 +1,199 @@
+//! On-disk checkpoints of print progress: serialized with CBOR, written
+//! atomically (temp file then rename), and schema-versioned.
+
+use crate::settings::Settings;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const SCHEMA_VERSION: u32 = 1;
+
+const CHECKPOINT_ACTIONS: usize = 200;
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The last axis position and heating targets known at checkpoint time, so
+/// a resumed print can restore them before the first post-resume action
+/// runs.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LastTargets {
+    pub axis: [f64; 3],
+    pub hotend_temp: f32,
+    pub bed_temp: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    version: u32,
+    pub source: PathBuf,
+    pub offset: u64,
+    pub settings: Settings,
+    pub last_targets: LastTargets,
+}
+
+impl Checkpoint {
+    pub fn new(source: PathBuf, offset: u64, settings: Settings, last_targets: LastTargets) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            source,
+            offset,
+            settings,
+            last_targets,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).context("couldn't open checkpoint file")?;
+        let checkpoint: Self =
+            serde_cbor::from_reader(file).context("couldn't decode checkpoint file")?;
+        if checkpoint.version != SCHEMA_VERSION {
+            bail!(
+                "checkpoint has schema version {}, only {} is supported",
+                checkpoint.version,
+                SCHEMA_VERSION
+            );
+        }
+        Ok(checkpoint)
+    }
+
+    /// Writes the checkpoint atomically: serialize to a temp file next to
+    /// `path`, then rename over it, so a reader never observes a
+    /// partially-written checkpoint.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let bytes = serde_cbor::to_vec(self).context("couldn't encode checkpoint")?;
+        let mut tmp_file =
+            File::create(&tmp_path).context("couldn't create temporary checkpoint file")?;
+        tmp_file.write_all(&bytes)?;
+        tmp_file.sync_all()?;
+        fs::rename(&tmp_path, path).context("couldn't install checkpoint file")?;
+        Ok(())
+    }
+
+    /// Whether a checkpoint file exists at `path`. Its contents and the
+    /// source file it names are not checked.
+    pub fn exists(path: &Path) -> bool {
+        path.is_file()
+    }
+
+    /// Removes the checkpoint at `path`, e.g. once a print it described has
+    /// finished or been stopped and there's nothing left to resume. Not an
+    /// error if no checkpoint is there.
+    pub fn remove(path: &Path) -> Result<()> {
+        match fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).context("couldn't remove checkpoint file"),
+        }
+    }
+}
+
+/// Tracks when the next checkpoint is due: after [`CHECKPOINT_ACTIONS`]
+/// actions, or after [`CHECKPOINT_INTERVAL`] has elapsed, whichever comes
+/// first.
+pub struct CheckpointSchedule {
+    path: PathBuf,
+    actions_since_last: usize,
+    last_saved: Instant,
+}
+
+impl CheckpointSchedule {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            actions_since_last: 0,
+            last_saved: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Call once per decoded action. Returns `true` (and resets the
+    /// schedule) once it's time to write a fresh checkpoint.
+    pub fn tick(&mut self) -> bool {
+        self.actions_since_last += 1;
+        if self.actions_since_last >= CHECKPOINT_ACTIONS
+            || self.last_saved.elapsed() >= CHECKPOINT_INTERVAL
+        {
+            self.actions_since_last = 0;
+            self.last_saved = Instant::now();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // unique per call so concurrent tests don't collide on the same path
+    fn temp_path(name: &str) -> PathBuf {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("ps3dprintd-checkpoint-test-{name}-{n}.cbor"))
+    }
+
+    fn sample(offset: u64) -> Checkpoint {
+        Checkpoint::new(
+            PathBuf::from("/tmp/print.gcode"),
+            offset,
+            Settings::default(),
+            LastTargets {
+                axis: [1.0, 2.0, 3.0],
+                hotend_temp: 200.0,
+                bed_temp: 60.0,
+            },
+        )
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp_path("round-trip");
+        let checkpoint = sample(1234);
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded.source, checkpoint.source);
+        assert_eq!(loaded.offset, checkpoint.offset);
+        assert_eq!(loaded.last_targets.axis, checkpoint.last_targets.axis);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_leaves_no_temp_file_behind() {
+        let path = temp_path("atomic");
+        sample(0).save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_rejects_mismatched_schema_version() {
+        let path = temp_path("version-mismatch");
+        let mut checkpoint = sample(0);
+        checkpoint.version = SCHEMA_VERSION + 1;
+        checkpoint.save(&path).unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn exists_and_remove_round_trip() {
+        let path = temp_path("exists");
+        assert!(!Checkpoint::exists(&path));
+        sample(0).save(&path).unwrap();
+        assert!(Checkpoint::exists(&path));
+        Checkpoint::remove(&path).unwrap();
+        assert!(!Checkpoint::exists(&path));
+        // removing again is a no-op, not an error
+        Checkpoint::remove(&path).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+//! Shared ring buffer of errors raised by the worker threads (decoder,
+//! executor, motor, heating), exposed through the `/errors` API.
+
+use crossbeam::channel::Receiver;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// how many errors are kept around before the oldest ones get evicted
+const CAPACITY: usize = 1000;
+const PAGE_SIZE: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    Decoder,
+    Executor,
+    Motor,
+    Heating,
+}
+
+/// A raw error as reported by one of the worker threads, before it has been
+/// assigned an id and timestamp.
+pub struct RecordedError {
+    pub subsystem: Subsystem,
+    pub message: String,
+}
+
+impl RecordedError {
+    pub fn new(subsystem: Subsystem, err: impl fmt::Display) -> Self {
+        Self {
+            subsystem,
+            message: err.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StoredError {
+    pub id: usize,
+    pub timestamp: u64,
+    pub subsystem: Subsystem,
+    pub message: String,
+}
+
+struct Inner {
+    // oldest first
+    errors: VecDeque<StoredError>,
+    next_id: usize,
+}
+
+/// Bounded ring buffer of recorded errors, cheaply cloneable so it can be
+/// handed to Rocket as managed state as well as to the thread that drains
+/// the error channel.
+#[derive(Clone)]
+pub struct ErrorStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ErrorStore {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                errors: VecDeque::with_capacity(CAPACITY),
+                next_id: 0,
+            })),
+        }
+    }
+
+    pub fn push(&self, error: RecordedError) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        if inner.errors.len() == CAPACITY {
+            inner.errors.pop_front();
+        }
+        inner.errors.push_back(StoredError {
+            id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            subsystem: error.subsystem,
+            message: error.message,
+        });
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<StoredError> {
+        let inner = self.inner.lock().unwrap();
+        inner.errors.iter().find(|e| e.id == id).cloned()
+    }
+
+    pub fn last(&self) -> Option<StoredError> {
+        let inner = self.inner.lock().unwrap();
+        inner.errors.back().cloned()
+    }
+
+    /// Most recent errors first, `PAGE_SIZE` per page.
+    pub fn page(&self, page: usize) -> Vec<StoredError> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .errors
+            .iter()
+            .rev()
+            .skip(page.saturating_mul(PAGE_SIZE))
+            .take(PAGE_SIZE)
+            .cloned()
+            .collect()
+    }
+
+    /// Spawns the thread that drains `error_recv` and records every error
+    /// the worker threads report, so callers don't need to take the store's
+    /// lock themselves.
+    pub fn spawn_recorder(self, error_recv: Receiver<RecordedError>) -> JoinHandle<()> {
+        thread::spawn(move || {
+            while let Ok(error) = error_recv.recv() {
+                self.push(error);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_n(store: &ErrorStore, n: usize) {
+        for _ in 0..n {
+            store.push(RecordedError::new(Subsystem::Decoder, "boom"));
+        }
+    }
+
+    #[test]
+    fn oldest_errors_are_evicted_past_capacity() {
+        let store = ErrorStore::new();
+        push_n(&store, CAPACITY + 10);
+        let inner = store.inner.lock().unwrap();
+        assert_eq!(inner.errors.len(), CAPACITY);
+        // ids 0..10 should have been evicted, 10 should now be the oldest
+        assert_eq!(inner.errors.front().unwrap().id, 10);
+        assert_eq!(inner.errors.back().unwrap().id, CAPACITY + 9);
+    }
+
+    #[test]
+    fn page_returns_most_recent_first() {
+        let store = ErrorStore::new();
+        push_n(&store, PAGE_SIZE * 2);
+        let first_page = store.page(0);
+        assert_eq!(first_page.len(), PAGE_SIZE);
+        assert_eq!(first_page[0].id, PAGE_SIZE * 2 - 1);
+        let second_page = store.page(1);
+        assert_eq!(second_page.len(), PAGE_SIZE);
+        assert_eq!(second_page.last().unwrap().id, 0);
+    }
+
+    #[test]
+    fn page_past_the_end_is_empty() {
+        let store = ErrorStore::new();
+        push_n(&store, PAGE_SIZE);
+        assert!(store.page(1).is_empty());
+    }
+
+    #[test]
+    fn page_does_not_panic_on_overflowing_index() {
+        let store = ErrorStore::new();
+        push_n(&store, 5);
+        assert!(store.page(usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn get_and_last_look_up_by_id() {
+        let store = ErrorStore::new();
+        push_n(&store, 3);
+        let last = store.last().unwrap();
+        assert_eq!(last.id, 2);
+        assert_eq!(store.get(1).unwrap().id, 1);
+        assert!(store.get(99).is_none());
+    }
+}
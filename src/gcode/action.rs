@@ -0,0 +1,17 @@
+//! Effects a decoded line of G-code can have on the machine -- the unit of
+//! work handed from the decoder thread to the executor thread.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Move to an absolute position. `None` on any field means "leave that
+    /// axis/extruder/feedrate where it already is".
+    Move {
+        axis: [Option<f64>; 3],
+        extrude: Option<f64>,
+        feedrate: Option<f64>,
+    },
+    SetHotendTemp(f32),
+    SetBedTemp(f32),
+    WaitHotendTemp(f32),
+    WaitBedTemp(f32),
+}
@@ -0,0 +1,142 @@
+//! Turns parsed G-code into [`Action`]s, tracking the axis position and
+//! heating targets implied by what's been decoded so far -- the state a
+//! checkpoint needs in order to restore a print after a crash.
+
+use super::action::Action;
+use crate::{checkpoint::LastTargets, settings::Settings};
+use anyhow::Result;
+use gcode::{GCode, Mnemonic};
+use std::collections::VecDeque;
+
+pub struct Decoder {
+    #[allow(dead_code)]
+    settings: Settings,
+    // axis position and heating targets implied by everything decoded so
+    // far; this is exactly what `last_targets()` hands to a checkpoint
+    last_targets: LastTargets,
+}
+
+impl Decoder {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            last_targets: LastTargets::default(),
+        }
+    }
+
+    /// Drops any in-flight decoding state on a stop. The tracked axis/
+    /// heating targets intentionally survive -- they describe where the
+    /// machine physically is, which a stop doesn't change.
+    pub fn reset(&mut self) {}
+
+    /// Decodes a single parsed G-code word into the action(s) it implies.
+    /// Codes this decoder doesn't recognize produce no action.
+    pub fn decode(&mut self, code: GCode) -> Result<Option<VecDeque<Action>>> {
+        let actions = match (code.mnemonic(), code.major_number()) {
+            (Mnemonic::General, 0) | (Mnemonic::General, 1) => self.decode_move(&code),
+            (Mnemonic::General, 92) => self.decode_set_position(&code),
+            (Mnemonic::Miscellaneous, 104) => self.decode_temp(&code, false, false),
+            (Mnemonic::Miscellaneous, 109) => self.decode_temp(&code, false, true),
+            (Mnemonic::Miscellaneous, 140) => self.decode_temp(&code, true, false),
+            (Mnemonic::Miscellaneous, 190) => self.decode_temp(&code, true, true),
+            _ => None,
+        };
+        Ok(actions)
+    }
+
+    fn arg(code: &GCode, letter: char) -> Option<f64> {
+        code.arguments()
+            .iter()
+            .find(|word| word.letter.eq_ignore_ascii_case(&letter))
+            .map(|word| word.value as f64)
+    }
+
+    // G0/G1: linear move, optionally extruding, optionally at a new
+    // feedrate. Updates the tracked axis position for whichever axes were
+    // actually given.
+    fn decode_move(&mut self, code: &GCode) -> Option<VecDeque<Action>> {
+        let axis = [
+            Self::arg(code, 'X'),
+            Self::arg(code, 'Y'),
+            Self::arg(code, 'Z'),
+        ];
+        let extrude = Self::arg(code, 'E');
+        let feedrate = Self::arg(code, 'F');
+        for (target, value) in self.last_targets.axis.iter_mut().zip(axis) {
+            if let Some(value) = value {
+                *target = value;
+            }
+        }
+        if axis.iter().all(Option::is_none) && extrude.is_none() && feedrate.is_none() {
+            return None;
+        }
+        Some(VecDeque::from([Action::Move {
+            axis,
+            extrude,
+            feedrate,
+        }]))
+    }
+
+    // G92: redefines the current position without moving -- just update
+    // what we think "here" is so later checkpoints stay accurate.
+    fn decode_set_position(&mut self, code: &GCode) -> Option<VecDeque<Action>> {
+        for (target, letter) in self.last_targets.axis.iter_mut().zip(['X', 'Y', 'Z']) {
+            if let Some(value) = Self::arg(code, letter) {
+                *target = value;
+            }
+        }
+        None
+    }
+
+    // M104/M109 (hotend) and M140/M190 (bed): set, or set-and-wait, a
+    // heating target.
+    fn decode_temp(&mut self, code: &GCode, bed: bool, wait: bool) -> Option<VecDeque<Action>> {
+        let temp = Self::arg(code, 'S')? as f32;
+        let action = match (bed, wait) {
+            (false, false) => {
+                self.last_targets.hotend_temp = temp;
+                Action::SetHotendTemp(temp)
+            }
+            (false, true) => {
+                self.last_targets.hotend_temp = temp;
+                Action::WaitHotendTemp(temp)
+            }
+            (true, false) => {
+                self.last_targets.bed_temp = temp;
+                Action::SetBedTemp(temp)
+            }
+            (true, true) => {
+                self.last_targets.bed_temp = temp;
+                Action::WaitBedTemp(temp)
+            }
+        };
+        Some(VecDeque::from([action]))
+    }
+
+    /// The axis position and heating targets implied by everything decoded
+    /// so far, for a checkpoint to save.
+    pub fn last_targets(&self) -> LastTargets {
+        self.last_targets.clone()
+    }
+
+    /// Actions that bring the machine back to `targets` before anything
+    /// from the resumed file runs, and seeds this decoder's own tracked
+    /// state so later moves and checkpoints are consistent with them.
+    /// Returns `None` if there's nothing to restore, i.e. resuming from the
+    /// very start of a print.
+    pub fn restore_targets(&mut self, targets: &LastTargets) -> Option<VecDeque<Action>> {
+        if *targets == LastTargets::default() {
+            return None;
+        }
+        self.last_targets = targets.clone();
+        Some(VecDeque::from([
+            Action::Move {
+                axis: targets.axis.map(Some),
+                extrude: None,
+                feedrate: None,
+            },
+            Action::SetBedTemp(targets.bed_temp),
+            Action::SetHotendTemp(targets.hotend_temp),
+        ]))
+    }
+}
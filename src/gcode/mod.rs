@@ -2,21 +2,26 @@ pub(self) mod action;
 mod decoder;
 pub mod error;
 mod executor;
+mod reader;
 
-use self::{action::Action, decoder::Decoder, executor::Executor};
+use self::{action::Action, decoder::Decoder, executor::Executor, reader::LineReader};
 use crate::{
+    checkpoint::{Checkpoint, CheckpointSchedule},
     comms::{ControlComms, DecoderComms, MotorControl},
+    error_store::{RecordedError, Subsystem},
+    job::{JobOutcome, JobQueue},
     settings::Settings,
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
 use crossbeam::{
     channel::{self, Receiver, Sender},
     select,
 };
-use gcode::GCode;
 use std::{
     collections::VecDeque,
+    fs::File,
     io::Read,
+    path::PathBuf,
     thread::{self, JoinHandle},
 };
 
@@ -25,10 +30,79 @@ use std::{
 // "lengthy" parsing can be sourced out ("parsing" could also then include already
 // calculating things etc. so actions only need to be executed)
 
-// FIXME make buffer only parts of the gcode from the file so we don't need
-// to store all of it in memory and can print arbitrarily large files
 struct PrintingState {
-    pub buf: VecDeque<Action>,
+    // kept around so checkpoints know which file to resume from
+    source: PathBuf,
+    reader: LineReader<Box<dyn Read + Send>>,
+    // each buffered action is paired with the reader's consumed offset
+    // *before* the line that produced it, so a checkpoint can resume at a
+    // line boundary nothing in the buffer has executed past yet -- using
+    // the reader's own (read-ahead) position instead would overshoot by
+    // whatever's sitting here un-executed
+    buf: VecDeque<(u64, Action)>,
+    // low/high watermarks: once buf drops below low_watermark, it's
+    // refilled from the reader up to high_watermark, so at most a bounded
+    // handful of actions are ever decoded ahead of the executor regardless
+    // of file size. Sourced from `Settings::tuning` so operators can trade
+    // memory for smoother motion without recompiling.
+    low_watermark: usize,
+    high_watermark: usize,
+}
+
+impl PrintingState {
+    pub fn new(
+        source: PathBuf,
+        file: Box<dyn Read + Send>,
+        low_watermark: usize,
+        high_watermark: usize,
+    ) -> Self {
+        Self {
+            source,
+            reader: LineReader::new(file),
+            buf: VecDeque::new(),
+            low_watermark,
+            high_watermark,
+        }
+    }
+
+    // pulls and decodes lines from the reader until buf reaches the high
+    // watermark or the reader runs out of lines
+    fn refill(&mut self, decoder: &mut Decoder) -> Result<()> {
+        while self.buf.len() < self.high_watermark {
+            let line_offset = self.reader.consumed_position();
+            match self.reader.next_line()? {
+                Some(line) => {
+                    for code in gcode::parse(line.as_str()) {
+                        if let Some(dq) = decoder.decode(code)? {
+                            self.buf.extend(dq.into_iter().map(|action| (line_offset, action)));
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn needs_refill(&self) -> bool {
+        self.buf.len() < self.low_watermark
+    }
+
+    // done once the reader is exhausted and everything it produced has been
+    // handed out
+    fn is_done(&self) -> bool {
+        self.buf.is_empty() && self.reader.is_eof()
+    }
+
+    // offset to resume from: the start of the oldest line that still has an
+    // action sitting in `buf`, or the reader's current position if
+    // everything decoded so far has already been handed out
+    fn checkpoint_offset(&self) -> u64 {
+        self.buf
+            .front()
+            .map(|(offset, _)| *offset)
+            .unwrap_or_else(|| self.reader.consumed_position())
+    }
 }
 
 enum InnerState {
@@ -49,18 +123,18 @@ impl State {
             printing_state: None,
         }
     }
-    pub fn print(&mut self, actions: VecDeque<Action>) {
+    pub fn print(&mut self, printing_state: PrintingState) {
         match self.state {
             InnerState::Printing => panic!("can't print, already printing"),
             InnerState::Paused => panic!("can't print, is paused"),
             InnerState::Stopped => {
                 self.state = InnerState::Printing;
-                self.printing_state = Some(PrintingState { buf: actions });
+                self.printing_state = Some(printing_state);
             }
         }
     }
 
-    pub fn stop(&mut self) {
+    fn stop(&mut self) {
         self.state = InnerState::Stopped;
         self.printing_state = None;
     }
@@ -88,6 +162,12 @@ impl State {
         }
     }
 
+    // true while a job is printing or paused, i.e. there's a job the
+    // supervisor is waiting to hear back about
+    pub fn is_active(&self) -> bool {
+        !matches!(self.state, InnerState::Stopped)
+    }
+
     pub fn printing_state_mut(&mut self) -> &mut PrintingState {
         match self.state {
             InnerState::Printing => self.printing_state.as_mut().unwrap(),
@@ -100,50 +180,177 @@ impl State {
 struct DecoderThread {
     pub decoder: Decoder,
     pub state: State,
+    settings: Settings,
+    // when set, progress is periodically written to disk so a print can
+    // survive a crash or power loss
+    checkpoint: Option<CheckpointSchedule>,
+    // action that's already been decoded but not yet handed to the executor,
+    // kept around so a decoder error can't cause it to be silently dropped
+    pending: Option<Action>,
 }
 
 impl DecoderThread {
-    pub fn new(decoder: Decoder) -> Self {
+    pub fn new(decoder: Decoder, settings: Settings, checkpoint_path: Option<PathBuf>) -> Self {
         Self {
             decoder,
             state: State::new(),
+            settings,
+            checkpoint: checkpoint_path.map(CheckpointSchedule::new),
+            pending: None,
+        }
+    }
+
+    // tears down the current print, discarding any action already decoded
+    // but not yet handed to the executor -- otherwise it would be sent to
+    // the executor as if it belonged to whatever job starts next
+    fn stop(&mut self) {
+        self.state.stop();
+        self.pending = None;
+    }
+
+    // nothing left to resume once a print has ended abnormally outside the
+    // `Stop` path above -- e.g. a decode error -- so it isn't silently
+    // resubmitted via `submit_resume` on the next restart
+    fn remove_checkpoint(&self) -> Result<()> {
+        if let Some(schedule) = &self.checkpoint {
+            Checkpoint::remove(schedule.path())?;
         }
+        Ok(())
     }
 
-    pub fn handle_msg(&mut self, msg: DecoderComms) -> Result<()> {
+    // returns whether this message stopped a print that was in progress, so
+    // the caller can let the job supervisor know the current job is done
+    pub fn handle_msg(&mut self, msg: DecoderComms) -> Result<bool> {
+        let was_active = self.state.is_active();
         match msg {
-            DecoderComms::Print(mut file) => {
-                let mut s = String::new();
-                file.read_to_string(&mut s)?;
-                let iter = gcode::parse(s.as_str());
-                let mut actions = VecDeque::with_capacity(iter.size_hint().0);
-                for code in iter {
-                    if let Some(dq) = self.decoder.decode(code)? {
-                        actions.extend(dq);
-                    }
-                }
-                self.state.print(actions);
+            DecoderComms::Print(source) => {
+                let file = File::open(&source).context("couldn't open gcode file")?;
+                let mut printing_state = PrintingState::new(
+                    source,
+                    Box::new(file),
+                    self.settings.tuning.decoder_low_watermark,
+                    self.settings.tuning.decoder_high_watermark,
+                );
+                // prime the buffer so the first next() call has something
+                // to hand out right away
+                printing_state.refill(&mut self.decoder)?;
+                self.state.print(printing_state);
+                Ok(false)
+            }
+            // sent by the job supervisor, never the API directly: see
+            // `JobQueue::submit_resume`
+            DecoderComms::Resume(checkpoint) => {
+                self.resume(checkpoint, true)?;
+                Ok(false)
             }
             DecoderComms::Stop => {
-                self.state.stop();
+                self.stop();
                 self.decoder.reset();
+                if was_active {
+                    self.remove_checkpoint()?;
+                }
+                Ok(was_active)
+            }
+            DecoderComms::Play => {
+                self.state.play();
+                Ok(false)
+            }
+            DecoderComms::Pause => {
+                self.state.pause();
+                Ok(false)
             }
-            DecoderComms::Play => self.state.play(),
-            DecoderComms::Pause => self.state.pause(),
-        };
+        }
+    }
+
+    /// Resumes a print from a previously saved checkpoint: re-opens the
+    /// source file, fast-forwards the streaming reader past whatever had
+    /// already been printed, restores the checkpointed settings, and queues
+    /// the actions needed to bring the machine back to its last known axis
+    /// and heating targets before anything from the file itself runs.
+    /// Transitions back into `Printing` (or `Paused`, pending user
+    /// confirmation).
+    pub fn resume(&mut self, checkpoint: Checkpoint, paused: bool) -> Result<()> {
+        self.settings = checkpoint.settings.clone();
+        self.decoder = Decoder::new(checkpoint.settings);
+        let file = File::open(&checkpoint.source).context("couldn't reopen gcode file")?;
+        let mut printing_state = PrintingState::new(
+            checkpoint.source,
+            Box::new(file),
+            self.settings.tuning.decoder_low_watermark,
+            self.settings.tuning.decoder_high_watermark,
+        );
+        printing_state.reader.skip(checkpoint.offset)?;
+        if let Some(restore) = self.decoder.restore_targets(&checkpoint.last_targets) {
+            let offset = printing_state.checkpoint_offset();
+            printing_state
+                .buf
+                .extend(restore.into_iter().map(|action| (offset, action)));
+        }
+        printing_state.refill(&mut self.decoder)?;
+        self.state.print(printing_state);
+        if paused {
+            self.state.pause();
+        }
         Ok(())
     }
 
-    fn next(&mut self) -> Action {
-        let print_state = self.state.printing_state_mut();
+    // returns the next action plus whether it was the last one of the
+    // current print (buffer drained and reader at eof)
+    fn next(&mut self) -> Result<(Action, bool)> {
+        let Self {
+            decoder,
+            state,
+            checkpoint,
+            ..
+        } = self;
+        let print_state = state.printing_state_mut();
+        if print_state.needs_refill() {
+            print_state.refill(decoder)?;
+        }
         // can't panic because there should always be something in the buffer,
         // if there is one
-        let action = print_state.buf.pop_front().unwrap();
-        // ensure there is something in the buffer:
-        if print_state.buf.is_empty() {
-            self.state.stop();
+        let (popped_offset, action) = print_state
+            .buf
+            .pop_front()
+            .context("gcode file has no action left to print")?;
+        let done = print_state.is_done();
+        // a line can decode into more than one action; if the next buffered
+        // action shares `popped_offset`, we're still partway through that
+        // line, and checkpointing now would record the line's start even
+        // though some of its actions have already been dispatched -- resume
+        // would then redo them. Only checkpoint at a line boundary.
+        let mid_line = print_state
+            .buf
+            .front()
+            .map_or(false, |(offset, _)| *offset == popped_offset);
+        // snapshot what's needed before `state.stop()` (which may follow)
+        // tears the printing state down
+        let mut checkpoint_data = None;
+        let mut finished_checkpoint_path = None;
+        if done {
+            // print finished cleanly: nothing left to resume
+            finished_checkpoint_path = checkpoint.as_ref().map(|schedule| schedule.path().to_owned());
+        } else if let Some(schedule) = checkpoint {
+            if !mid_line && schedule.tick() {
+                checkpoint_data = Some((
+                    print_state.source.clone(),
+                    print_state.checkpoint_offset(),
+                    decoder.last_targets(),
+                    schedule.path().to_owned(),
+                ));
+            }
+        }
+        if done {
+            state.stop();
+        }
+        if let Some((source, offset, last_targets, checkpoint_path)) = checkpoint_data {
+            Checkpoint::new(source, offset, self.settings.clone(), last_targets)
+                .save(&checkpoint_path)?;
         }
-        action
+        if let Some(checkpoint_path) = finished_checkpoint_path {
+            Checkpoint::remove(&checkpoint_path)?;
+        }
+        Ok((action, done))
     }
 }
 
@@ -151,22 +358,111 @@ fn decoder_loop(
     settings: Settings,
     decoder_recv: Receiver<ControlComms<DecoderComms>>,
     executor_send: Sender<ControlComms<Action>>,
+    error_send: Sender<RecordedError>,
+    job_done_send: Sender<JobOutcome>,
+    checkpoint_path: Option<PathBuf>,
+    queue: JobQueue,
 ) {
-    let mut data = DecoderThread::new(Decoder::new(settings));
+    let mut data = DecoderThread::new(
+        Decoder::new(settings.clone()),
+        settings,
+        checkpoint_path.clone(),
+    );
+    if let Some(path) = checkpoint_path.as_deref().filter(|p| Checkpoint::exists(p)) {
+        // queued rather than resumed directly, so it's driven through the
+        // same handle_msg path as any other job instead of racing the
+        // supervisor's own view of what's printing
+        match Checkpoint::load(path) {
+            Ok(checkpoint) => {
+                queue.submit_resume(checkpoint);
+            }
+            Err(e) => {
+                error_send
+                    .send(RecordedError::new(Subsystem::Decoder, e))
+                    .unwrap();
+            }
+        }
+    }
     loop {
         if data.state.is_printing() {
+            if data.pending.is_none() {
+                match data.next() {
+                    Ok((action, finished)) => {
+                        data.pending = Some(action);
+                        if finished {
+                            job_done_send.send(JobOutcome::Done).unwrap();
+                        }
+                    }
+                    Err(e) => {
+                        let message = e.to_string();
+                        error_send
+                            .send(RecordedError::new(Subsystem::Decoder, message.clone()))
+                            .unwrap();
+                        data.stop();
+                        // this print is dead and the offset it last checkpointed
+                        // at is now meaningless, so there's nothing to resume
+                        if let Err(e) = data.remove_checkpoint() {
+                            error_send
+                                .send(RecordedError::new(Subsystem::Decoder, e))
+                                .unwrap();
+                        }
+                        job_done_send.send(JobOutcome::Failed(message)).unwrap();
+                        continue;
+                    }
+                }
+            }
             select! {
                 recv(decoder_recv) -> msg => match msg.unwrap() {
-                    // FIXME do smth with result
-                    ControlComms::Msg(m) => data.handle_msg(m).unwrap(),
+                    ControlComms::Msg(m) => match data.handle_msg(m) {
+                        Ok(aborted) => {
+                            if aborted {
+                                job_done_send
+                                    .send(JobOutcome::Failed("print was stopped".to_owned()))
+                                    .unwrap();
+                            }
+                        }
+                        Err(e) => {
+                            // a job was in flight (we're inside is_printing()),
+                            // so the supervisor is blocked waiting to hear
+                            // back about it -- tell it the job failed, or it
+                            // hangs forever and the whole queue stalls
+                            let message = e.to_string();
+                            error_send
+                                .send(RecordedError::new(Subsystem::Decoder, message.clone()))
+                                .unwrap();
+                            data.stop();
+                            job_done_send.send(JobOutcome::Failed(message)).unwrap();
+                        }
+                    },
                     ControlComms::Exit => break,
                 },
-                send(executor_send, ControlComms::Msg(data.next())) -> res => res.unwrap()
+                send(executor_send, ControlComms::Msg(data.pending.take().unwrap())) -> res => res.unwrap()
             }
         } else {
+            // captured before handling the message: a paused print still
+            // has a job the supervisor is waiting to hear back about, even
+            // though we're not actively printing right now
+            let was_active = data.state.is_active();
             match decoder_recv.recv().unwrap() {
-                // FIXME do smth with result
-                ControlComms::Msg(m) => data.handle_msg(m).unwrap(),
+                ControlComms::Msg(m) => match data.handle_msg(m) {
+                    Ok(aborted) => {
+                        if aborted {
+                            job_done_send
+                                .send(JobOutcome::Failed("print was stopped".to_owned()))
+                                .unwrap();
+                        }
+                    }
+                    Err(e) => {
+                        let message = e.to_string();
+                        error_send
+                            .send(RecordedError::new(Subsystem::Decoder, message.clone()))
+                            .unwrap();
+                        data.stop();
+                        if was_active {
+                            job_done_send.send(JobOutcome::Failed(message)).unwrap();
+                        }
+                    }
+                },
                 ControlComms::Exit => break,
             }
         }
@@ -177,12 +473,24 @@ fn executor_loop(
     action_recv: Receiver<ControlComms<Action>>,
     motor_send: Sender<MotorControl>,
     motor_ret_recv: Receiver<Result<()>>,
+    decoder_send: Sender<ControlComms<DecoderComms>>,
+    error_send: Sender<RecordedError>,
 ) {
     let mut exec = Executor::new(motor_send, motor_ret_recv);
     loop {
         match action_recv.recv().unwrap() {
-            // FIXME do something with result
-            ControlComms::Msg(a) => exec.exec(a).unwrap(),
+            ControlComms::Msg(a) => {
+                if let Err(e) = exec.exec(a) {
+                    error_send
+                        .send(RecordedError::new(Subsystem::Executor, e))
+                        .unwrap();
+                    // the decoder has no way to know the action it handed
+                    // off failed, so tell it to abort the print
+                    decoder_send
+                        .send(ControlComms::Msg(DecoderComms::Stop))
+                        .unwrap();
+                }
+            }
             ControlComms::Exit => break,
         }
     }
@@ -191,12 +499,36 @@ fn executor_loop(
 pub fn start(
     settings: Settings,
     decoder_recv: Receiver<ControlComms<DecoderComms>>,
+    decoder_send: Sender<ControlComms<DecoderComms>>,
     motor_send: Sender<MotorControl>,
     motor_ret_recv: Receiver<Result<()>>,
+    error_send: Sender<RecordedError>,
+    job_done_send: Sender<JobOutcome>,
+    checkpoint_path: Option<PathBuf>,
+    queue: JobQueue,
 ) -> (JoinHandle<()>, JoinHandle<()>) {
-    let (executor_send, executor_recv) = channel::bounded(16);
-    let executor_handle =
-        thread::spawn(move || executor_loop(executor_recv, motor_send, motor_ret_recv));
-    let decoder_handle = thread::spawn(move || decoder_loop(settings, decoder_recv, executor_send));
+    let (executor_send, executor_recv) =
+        channel::bounded(settings.tuning.executor_channel_capacity);
+    let executor_error_send = error_send.clone();
+    let executor_handle = thread::spawn(move || {
+        executor_loop(
+            executor_recv,
+            motor_send,
+            motor_ret_recv,
+            decoder_send,
+            executor_error_send,
+        )
+    });
+    let decoder_handle = thread::spawn(move || {
+        decoder_loop(
+            settings,
+            decoder_recv,
+            executor_send,
+            error_send,
+            job_done_send,
+            checkpoint_path,
+            queue,
+        )
+    });
     (decoder_handle, executor_handle)
 }
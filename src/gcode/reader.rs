@@ -0,0 +1,174 @@
+//! Incremental line reader for streaming G-code off disk in fixed-size
+//! chunks, modeled on the buffering scheme `tokio_util::codec::Framed` uses
+//! internally (the `eof`/`is_readable` flags mirror `FramedImpl`'s).
+
+use anyhow::Result;
+use bytes::BytesMut;
+use std::io::Read;
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+pub struct LineReader<R> {
+    inner: R,
+    buf: BytesMut,
+    // true once `inner` has returned 0 bytes
+    eof: bool,
+    // true while `buf` might still contain a line worth scanning for
+    is_readable: bool,
+    // total bytes pulled out of `inner` so far, used to checkpoint how far
+    // into the source this reader has progressed
+    position: u64,
+}
+
+impl<R: Read> LineReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: BytesMut::new(),
+            eof: false,
+            is_readable: false,
+            position: 0,
+        }
+    }
+
+    /// Returns the next complete line with its line ending stripped, or
+    /// `None` once the reader is at eof and no partial line is left over.
+    pub fn next_line(&mut self) -> Result<Option<String>> {
+        loop {
+            if self.is_readable {
+                if let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+                    let mut line = self.buf.split_to(pos + 1);
+                    line.truncate(pos);
+                    if line.last() == Some(&b'\r') {
+                        line.truncate(line.len() - 1);
+                    }
+                    return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+                }
+                if self.eof {
+                    self.is_readable = false;
+                    if self.buf.is_empty() {
+                        return Ok(None);
+                    }
+                    let rest = self.buf.split_to(self.buf.len());
+                    return Ok(Some(String::from_utf8_lossy(&rest).into_owned()));
+                }
+                // no full line buffered yet, need to read more
+                self.is_readable = false;
+            }
+            if self.eof {
+                return Ok(None);
+            }
+            let mut chunk = [0u8; CHUNK_SIZE];
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                self.eof = true;
+            } else {
+                self.buf.extend_from_slice(&chunk[..n]);
+                self.position += n as u64;
+            }
+            self.is_readable = true;
+        }
+    }
+
+    /// Whether the underlying reader has been fully drained.
+    pub fn is_eof(&self) -> bool {
+        self.eof && self.buf.is_empty()
+    }
+
+    /// How many bytes have actually been returned to the caller as part of
+    /// a line: the total pulled from the underlying reader, with whatever's
+    /// sitting unread in `buf` (read ahead in the last chunk, or a trailing
+    /// partial line) backed out. This is the only offset safe to resume
+    /// from -- the raw read count alone overshoots by up to a chunk,
+    /// silently skipping anything buffered but not yet handed out.
+    pub fn consumed_position(&self) -> u64 {
+        self.position - self.buf.len() as u64
+    }
+
+    /// Discards `bytes` from the underlying reader without decoding them,
+    /// used to fast-forward a freshly (re-)opened reader to a checkpointed
+    /// offset.
+    pub fn skip(&mut self, bytes: u64) -> Result<()> {
+        let mut remaining = bytes;
+        let mut sink = [0u8; CHUNK_SIZE];
+        while remaining > 0 {
+            let want = remaining.min(CHUNK_SIZE as u64) as usize;
+            let n = self.inner.read(&mut sink[..want])?;
+            if n == 0 {
+                self.eof = true;
+                break;
+            }
+            remaining -= n as u64;
+        }
+        self.position += bytes - remaining;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // hands back at most `chunk_len` bytes per call regardless of how much
+    // the caller asked for, so a line can arrive split across several of
+    // `LineReader`'s own chunked reads instead of in one shot
+    struct SlowReader {
+        data: Vec<u8>,
+        pos: usize,
+        chunk_len: usize,
+    }
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let remaining = &self.data[self.pos..];
+            let n = remaining.len().min(buf.len()).min(self.chunk_len);
+            buf[..n].copy_from_slice(&remaining[..n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn splits_line_across_multiple_underlying_reads() {
+        let reader = SlowReader {
+            data: b"G1 X10\nG1 Y20\n".to_vec(),
+            pos: 0,
+            chunk_len: 3,
+        };
+        let mut lines = LineReader::new(reader);
+        assert_eq!(lines.next_line().unwrap(), Some("G1 X10".to_owned()));
+        assert_eq!(lines.next_line().unwrap(), Some("G1 Y20".to_owned()));
+        assert_eq!(lines.next_line().unwrap(), None);
+    }
+
+    #[test]
+    fn strips_crlf_line_endings() {
+        let mut lines = LineReader::new(Cursor::new(b"G1 X10\r\nG1 Y20\r\n".to_vec()));
+        assert_eq!(lines.next_line().unwrap(), Some("G1 X10".to_owned()));
+        assert_eq!(lines.next_line().unwrap(), Some("G1 Y20".to_owned()));
+    }
+
+    #[test]
+    fn returns_trailing_line_with_no_final_newline_at_eof() {
+        let mut lines = LineReader::new(Cursor::new(b"G1 X10\nG1 Y20".to_vec()));
+        assert_eq!(lines.next_line().unwrap(), Some("G1 X10".to_owned()));
+        assert_eq!(lines.next_line().unwrap(), Some("G1 Y20".to_owned()));
+        assert!(lines.is_eof());
+        assert_eq!(lines.next_line().unwrap(), None);
+    }
+
+    #[test]
+    fn consumed_position_excludes_unhandled_buffer() {
+        let mut lines = LineReader::new(Cursor::new(b"G1 X10\nG1 Y20\n".to_vec()));
+        lines.next_line().unwrap();
+        assert_eq!(lines.consumed_position(), 7);
+    }
+
+    #[test]
+    fn skip_past_eof_stops_cleanly_instead_of_looping() {
+        let mut lines = LineReader::new(Cursor::new(b"short".to_vec()));
+        lines.skip(100).unwrap();
+        assert!(lines.is_eof());
+    }
+}
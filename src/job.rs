@@ -0,0 +1,375 @@
+//! FIFO print-job queue, fed by the API and drained one job at a time by
+//! [`run_supervisor`], which drives the decoder thread through it.
+
+use crate::{
+    checkpoint::Checkpoint,
+    comms::{ControlComms, DecoderComms},
+    worker::Worker,
+};
+use anyhow::{anyhow, Result};
+use crossbeam::channel::{Receiver, Sender};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// how long the supervisor sleeps between checks when the queue is empty
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub type JobId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Printing,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PrintJob {
+    pub id: JobId,
+    pub source: PathBuf,
+    pub submitted_at: u64,
+    pub status: JobStatus,
+    // set for a job that's resuming from a checkpoint rather than starting
+    // `source` fresh; internal only, never part of the public API shape
+    #[serde(skip)]
+    resume: Option<Checkpoint>,
+}
+
+/// What became of a job the supervisor handed to the decoder thread.
+pub enum JobOutcome {
+    Done,
+    Failed(String),
+}
+
+struct Inner {
+    // FIFO order; the front is the next job to run
+    jobs: VecDeque<PrintJob>,
+    next_id: JobId,
+}
+
+/// Shared FIFO queue of print jobs, fed by the API and drained by
+/// [`run_supervisor`].
+#[derive(Clone)]
+pub struct JobQueue {
+    inner: Arc<Mutex<Inner>>,
+    // so cancelling the job that's currently printing can actually stop it,
+    // not just flag it in the queue
+    decoder_send: Sender<ControlComms<DecoderComms>>,
+    // job sources submitted through the API are resolved against this
+    // directory and rejected if they'd escape it, so a request can't be used
+    // to open arbitrary files on the host; must already be canonical
+    gcode_dir: PathBuf,
+}
+
+impl JobQueue {
+    pub fn new(decoder_send: Sender<ControlComms<DecoderComms>>, gcode_dir: PathBuf) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                jobs: VecDeque::new(),
+                next_id: 0,
+            })),
+            decoder_send,
+            gcode_dir,
+        }
+    }
+
+    /// Queues `source` to print, resolved against the configured gcode
+    /// directory. Returns `None` if `source` is absolute or otherwise
+    /// escapes that directory (e.g. via `..`), since this takes a path
+    /// straight from an untrusted API request.
+    pub fn submit(&self, source: PathBuf) -> Option<JobId> {
+        let source = self.resolve_source(source)?;
+        Some(self.push(source, None))
+    }
+
+    fn resolve_source(&self, source: PathBuf) -> Option<PathBuf> {
+        if source.is_absolute() {
+            return None;
+        }
+        let resolved = self.gcode_dir.join(source).canonicalize().ok()?;
+        resolved.starts_with(&self.gcode_dir).then_some(resolved)
+    }
+
+    /// Queues a job that resumes from `checkpoint` instead of starting its
+    /// source file fresh. Used only by the decoder thread's startup logic,
+    /// so a resumed print is driven through the same queue/supervisor path
+    /// as any other job instead of mutating the decoder's state directly.
+    pub(crate) fn submit_resume(&self, checkpoint: Checkpoint) -> JobId {
+        let source = checkpoint.source.clone();
+        self.push(source, Some(checkpoint))
+    }
+
+    fn push(&self, source: PathBuf, resume: Option<Checkpoint>) -> JobId {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.jobs.push_back(PrintJob {
+            id,
+            source,
+            submitted_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            status: JobStatus::Queued,
+            resume,
+        });
+        id
+    }
+
+    /// Takes the first still-queued job (it stays in the list), marks it
+    /// `Printing`, and dispatches
+    /// it to the decoder thread over `decoder_send` -- all under the same
+    /// lock `cancel` takes, so a cancel can never land in the gap between
+    /// "marked Printing" and "actually sent" and silently let a cancelled
+    /// job run to completion anyway.
+    fn start_next(&self, decoder_send: &Sender<ControlComms<DecoderComms>>) -> Option<JobId> {
+        let mut inner = self.inner.lock().unwrap();
+        let job = inner.jobs.iter_mut().find(|j| j.status == JobStatus::Queued)?;
+        job.status = JobStatus::Printing;
+        let id = job.id;
+        let msg = match job.resume.clone() {
+            Some(checkpoint) => DecoderComms::Resume(checkpoint),
+            None => DecoderComms::Print(job.source.clone()),
+        };
+        decoder_send.send(ControlComms::Msg(msg)).unwrap();
+        Some(id)
+    }
+
+    fn finish(&self, id: JobId, status: JobStatus) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(job) = inner.jobs.iter_mut().find(|j| j.id == id) {
+            // a cancel that raced this is the authoritative outcome, not
+            // whatever the supervisor observed the stopped job do
+            if job.status != JobStatus::Cancelled {
+                job.status = status;
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<PrintJob> {
+        self.inner.lock().unwrap().jobs.iter().cloned().collect()
+    }
+
+    /// Moves a still-queued job to `new_index` in the FIFO order. Returns
+    /// `false` if there's no such queued job.
+    pub fn reorder(&self, id: JobId, new_index: usize) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let pos = match inner.jobs.iter().position(|j| j.id == id) {
+            Some(pos) if inner.jobs[pos].status == JobStatus::Queued => pos,
+            _ => return false,
+        };
+        let job = inner.jobs.remove(pos).unwrap();
+        let new_index = new_index.min(inner.jobs.len());
+        inner.jobs.insert(new_index, job);
+        true
+    }
+
+    /// Cancels a job: a still-queued one is removed outright, the one
+    /// currently printing is flagged `Cancelled` and actually stopped by
+    /// sending the decoder thread a [`DecoderComms::Stop`]. Returns `false`
+    /// if there's no such job, or it's already finished.
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let pos = match inner.jobs.iter().position(|j| j.id == id) {
+            Some(pos) => pos,
+            None => return false,
+        };
+        match inner.jobs[pos].status {
+            JobStatus::Queued => {
+                inner.jobs.remove(pos);
+                true
+            }
+            JobStatus::Printing => {
+                inner.jobs[pos].status = JobStatus::Cancelled;
+                drop(inner);
+                self.decoder_send
+                    .send(ControlComms::Msg(DecoderComms::Stop))
+                    .unwrap();
+                true
+            }
+            JobStatus::Done | JobStatus::Failed | JobStatus::Cancelled => false,
+        }
+    }
+}
+
+/// Waits for the decoder thread to report how the job currently in flight
+/// went. The dispatch itself happens in [`JobQueue::start_next`], atomically
+/// with marking the job `Printing`, so by the time a `PrintWorker` runs the
+/// corresponding `Print`/`Resume` is already guaranteed to be on its way.
+struct PrintWorker<'a> {
+    job_done_recv: &'a Receiver<JobOutcome>,
+}
+
+impl Worker for PrintWorker<'_> {
+    fn work(&mut self) -> Result<()> {
+        match self.job_done_recv.recv().unwrap() {
+            JobOutcome::Done => Ok(()),
+            JobOutcome::Failed(message) => Err(anyhow!(message)),
+        }
+    }
+}
+
+/// Spawns the thread that feeds the decoder thread one job at a time:
+/// picks and dispatches the next queued job, waits for it to finish via
+/// [`PrintWorker`], records its outcome, and automatically moves on to the
+/// next one.
+pub fn run_supervisor(
+    queue: JobQueue,
+    decoder_send: Sender<ControlComms<DecoderComms>>,
+    job_done_recv: Receiver<JobOutcome>,
+) -> JoinHandle<()> {
+    thread::spawn(move || loop {
+        let id = match queue.start_next(&decoder_send) {
+            Some(id) => id,
+            None => {
+                sleep(IDLE_POLL_INTERVAL);
+                continue;
+            }
+        };
+        let mut worker = PrintWorker {
+            job_done_recv: &job_done_recv,
+        };
+        // the decoder thread already reports the underlying cause of a
+        // failure over `error_send` itself; here we just track the job
+        let status = match worker.work() {
+            Ok(()) => JobStatus::Done,
+            Err(_) => JobStatus::Failed,
+        };
+        queue.finish(id, status);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam::channel::unbounded;
+    use std::{
+        fs,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    // a fresh, real directory per test so `submit`'s canonicalize-and-check
+    // has something genuine to resolve against, with one gcode file in it
+    fn gcode_dir() -> PathBuf {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("ps3dprintd-job-test-{n}"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.gcode"), b"").unwrap();
+        fs::write(dir.join("b.gcode"), b"").unwrap();
+        dir
+    }
+
+    fn queue() -> (JobQueue, Receiver<ControlComms<DecoderComms>>, PathBuf) {
+        let (decoder_send, decoder_recv) = unbounded();
+        let dir = gcode_dir();
+        (JobQueue::new(decoder_send, dir.clone()), decoder_recv, dir)
+    }
+
+    #[test]
+    fn submit_assigns_ids_in_fifo_order() {
+        let (queue, _decoder_recv, _dir) = queue();
+        let a = queue.submit(PathBuf::from("a.gcode")).unwrap();
+        let b = queue.submit(PathBuf::from("b.gcode")).unwrap();
+        let ids: Vec<_> = queue.list().iter().map(|j| j.id).collect();
+        assert_eq!(ids, vec![a, b]);
+        assert!(queue.list().iter().all(|j| j.status == JobStatus::Queued));
+    }
+
+    #[test]
+    fn submit_rejects_an_absolute_path() {
+        let (queue, _decoder_recv, _dir) = queue();
+        assert!(queue.submit(PathBuf::from("/etc/passwd")).is_none());
+    }
+
+    #[test]
+    fn submit_rejects_a_path_that_escapes_the_gcode_dir() {
+        let (queue, _decoder_recv, _dir) = queue();
+        assert!(queue.submit(PathBuf::from("../../etc/passwd")).is_none());
+    }
+
+    #[test]
+    fn reorder_moves_a_queued_job() {
+        let (queue, _decoder_recv, _dir) = queue();
+        let a = queue.submit(PathBuf::from("a.gcode")).unwrap();
+        let b = queue.submit(PathBuf::from("b.gcode")).unwrap();
+        assert!(queue.reorder(b, 0));
+        let ids: Vec<_> = queue.list().iter().map(|j| j.id).collect();
+        assert_eq!(ids, vec![b, a]);
+    }
+
+    #[test]
+    fn reorder_rejects_a_job_that_is_not_queued() {
+        let (queue, _decoder_recv, _dir) = queue();
+        let id = queue.submit(PathBuf::from("a.gcode")).unwrap();
+        queue.start_next(&queue.decoder_send);
+        assert!(!queue.reorder(id, 0));
+    }
+
+    #[test]
+    fn cancel_removes_a_queued_job_outright() {
+        let (queue, _decoder_recv, _dir) = queue();
+        let id = queue.submit(PathBuf::from("a.gcode")).unwrap();
+        assert!(queue.cancel(id));
+        assert!(queue.list().is_empty());
+        // already gone, so cancelling it again fails
+        assert!(!queue.cancel(id));
+    }
+
+    #[test]
+    fn cancel_stops_the_job_that_is_printing() {
+        let (queue, decoder_recv, _dir) = queue();
+        let id = queue.submit(PathBuf::from("a.gcode")).unwrap();
+        queue.start_next(&queue.decoder_send);
+        assert!(queue.cancel(id));
+        let status = queue.list().into_iter().find(|j| j.id == id).unwrap().status;
+        assert_eq!(status, JobStatus::Cancelled);
+        // the dispatch and the Stop it triggers should both have gone out
+        assert!(matches!(
+            decoder_recv.recv().unwrap(),
+            ControlComms::Msg(DecoderComms::Print(_))
+        ));
+        assert!(matches!(
+            decoder_recv.recv().unwrap(),
+            ControlComms::Msg(DecoderComms::Stop)
+        ));
+    }
+
+    #[test]
+    fn cancel_outranks_a_finish_that_races_it() {
+        let (queue, _decoder_recv, _dir) = queue();
+        let id = queue.submit(PathBuf::from("a.gcode")).unwrap();
+        queue.start_next(&queue.decoder_send);
+        assert!(queue.cancel(id));
+        // the supervisor's own view of how the job ended loses to the cancel
+        queue.finish(id, JobStatus::Done);
+        let status = queue.list().into_iter().find(|j| j.id == id).unwrap().status;
+        assert_eq!(status, JobStatus::Cancelled);
+    }
+
+    #[test]
+    fn submit_resume_queues_a_resume_message() {
+        let (queue, decoder_recv, dir) = queue();
+        let checkpoint = Checkpoint::new(
+            dir.join("a.gcode"),
+            42,
+            crate::settings::Settings::default(),
+            crate::checkpoint::LastTargets::default(),
+        );
+        queue.submit_resume(checkpoint);
+        queue.start_next(&queue.decoder_send);
+        assert!(matches!(
+            decoder_recv.recv().unwrap(),
+            ControlComms::Msg(DecoderComms::Resume(_))
+        ));
+    }
+}
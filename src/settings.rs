@@ -0,0 +1,43 @@
+//! Tuning knobs that aren't part of the machine profile itself, but trade
+//! memory for smoother motion on fast machines or constrained SBCs. These
+//! live alongside the rest of the daemon's configuration.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TuningSettings {
+    /// Capacity of the bounded channel handing decoded actions from the
+    /// decoder thread to the executor thread.
+    pub executor_channel_capacity: usize,
+    /// Once the streaming decoder's action buffer drops below this many
+    /// actions, it's refilled from the G-code reader.
+    pub decoder_low_watermark: usize,
+    /// The streaming decoder refills its action buffer up to this many
+    /// actions before pausing again.
+    pub decoder_high_watermark: usize,
+}
+
+impl Default for TuningSettings {
+    fn default() -> Self {
+        Self {
+            executor_channel_capacity: 16,
+            decoder_low_watermark: 64,
+            decoder_high_watermark: 256,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub tuning: TuningSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tuning: TuningSettings::default(),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! A small trait for running a unit of work to completion and reporting
+//! how it went.
+
+use anyhow::Result;
+
+/// A unit of work that can be driven to completion by a supervisor.
+/// `Ok(())` means it finished successfully; `Err` carries the cause of
+/// failure.
+pub trait Worker {
+    fn work(&mut self) -> Result<()>;
+}